@@ -1,15 +1,14 @@
-use super::BodyIoError;
+use super::{BodyIoError, Message};
 
 #[allow(unused_imports)]
 use crate::BodyWriter;
 
 use core::{
-    future::Future,
-    pin::{pin, Pin},
+    pin::Pin,
     task::{Context, Poll},
 };
 
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use tokio::sync::mpsc::Receiver;
 
 /// A reader for the body of an HTTP request or response.
@@ -17,7 +16,14 @@ use tokio::sync::mpsc::Receiver;
 /// This reader implements the [`http_body::Body`] trait and is used for
 /// web servers to access the data being sent by the [`BodyWriter`].
 pub struct BodyReader {
-    pub(crate) receiver: Receiver<Bytes>,
+    pub(crate) receiver: Receiver<Message>,
+    /// Out-of-band error set by [`BodyWriter::abort`]; resolves to a
+    /// [`BodyIoError`] when the body was truncated and closes cleanly otherwise.
+    pub(crate) error: tokio::sync::oneshot::Receiver<BodyIoError>,
+    /// Remaining body length when an exact content length is known.
+    pub(crate) remaining: Option<u64>,
+    /// Leftover bytes from a chunk that did not fit in the caller's buffer.
+    pub(crate) buffer: Bytes,
 }
 
 impl std::fmt::Debug for BodyReader {
@@ -35,36 +41,101 @@ impl http_body::Body for BodyReader {
     ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
         let this = self.get_mut();
         match this.receiver.poll_recv(cx) {
-            Poll::Ready(Some(bytes)) => {
+            Poll::Ready(Some(Message::Data(bytes))) => {
+                if let Some(remaining) = this.remaining.as_mut() {
+                    *remaining = remaining.saturating_sub(bytes.len() as u64);
+                }
                 let frame = http_body::Frame::data(bytes);
                 Poll::Ready(Some(Ok(frame)))
             }
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => {
-                cx.waker().wake_by_ref();
-                Poll::Pending
+            Poll::Ready(Some(Message::Trailers(trailers))) => {
+                let frame = http_body::Frame::trailers(*trailers);
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(None) => match this.error.try_recv() {
+                Ok(err) => Poll::Ready(Some(Err(err))),
+                Err(_) => Poll::Ready(None),
+            },
+            // `poll_recv` has registered our waker against the channel; it will
+            // wake us when a frame arrives, so there is no need to self-wake.
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        match self.remaining {
+            Some(remaining) => http_body::SizeHint::with_exact(remaining),
+            None => http_body::SizeHint::default(),
+        }
+    }
+}
+
+impl BodyReader {
+    /// Ensure [`buffer`](Self::buffer) holds the next chunk of body bytes.
+    ///
+    /// Returns `Ready(Ok(()))` with a non-empty buffer when data is available,
+    /// `Ready(Ok(()))` with an empty buffer at the clean end of stream (trailers
+    /// carry no bytes), and `Ready(Err(..))` if the writer aborted the body.
+    /// Empty data chunks are skipped so the buffer is never left spuriously empty.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        while self.buffer.is_empty() {
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(Message::Data(bytes))) => {
+                    // Account for the chunk against the declared length as it is
+                    // received, exactly as `poll_frame` does, so `size_hint`
+                    // stays accurate for consumers draining via `AsyncRead`.
+                    if let Some(remaining) = self.remaining.as_mut() {
+                        *remaining = remaining.saturating_sub(bytes.len() as u64);
+                    }
+                    self.buffer = bytes;
+                }
+                // Trailers carry no body bytes, so the byte stream ends here.
+                Poll::Ready(Some(Message::Trailers(_))) => return Poll::Ready(Ok(())),
+                Poll::Ready(None) => {
+                    return match self.error.try_recv() {
+                        Ok(err) => Poll::Ready(Err(std::io::Error::other(err))),
+                        Err(_) => Poll::Ready(Ok(())),
+                    };
+                }
+                // `poll_recv` has parked our waker on the channel; no self-wake.
+                Poll::Pending => return Poll::Pending,
             }
         }
+        Poll::Ready(Ok(()))
     }
 }
 
 impl tokio::io::AsyncRead for BodyReader {
     fn poll_read(
-        mut self: Pin<&mut Self>,
+        self: Pin<&mut Self>,
         cx: &mut Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> Poll<std::io::Result<()>> {
-        let mut this = pin!(self.receiver.recv());
-        match this.as_mut().poll(cx) {
-            Poll::Pending => {
-                cx.waker().wake_by_ref();
-                Poll::Pending
-            }
-            Poll::Ready(Some(bytes)) => {
-                buf.put_slice(&bytes);
+        let this = self.get_mut();
+        match this.poll_fill(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Ready(Ok(())) => {
+                let n = std::cmp::min(buf.remaining(), this.buffer.len());
+                buf.put_slice(&this.buffer[..n]);
+                this.buffer.advance(n);
                 Poll::Ready(Ok(()))
             }
-            Poll::Ready(None) => Poll::Ready(Ok(())),
         }
     }
 }
+
+impl tokio::io::AsyncBufRead for BodyReader {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<&[u8]>> {
+        let this = self.get_mut();
+        match this.poll_fill(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(&this.buffer[..])),
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().buffer.advance(amt);
+    }
+}
@@ -1,11 +1,27 @@
 use core::{
     future::Future,
-    pin::{pin, Pin},
+    pin::Pin,
     task::{Context, Poll},
 };
 
 use bytes::Bytes;
-use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{error::SendError, OwnedPermit};
+
+use crate::Message;
+
+/// A channel reservation owned by the async writer.
+///
+/// The `reserve_owned` future has to survive the `Poll::Pending` it returns on
+/// a full channel: dropping it would pull our waiter out of the channel's
+/// semaphore queue, so freeing capacity on the reader side would never wake us.
+/// We therefore keep the in-flight future here between polls.
+type ReserveFuture =
+    Pin<Box<dyn Future<Output = Result<OwnedPermit<Message>, SendError<()>>> + Send>>;
+
+pub(crate) enum WriteState {
+    Idle,
+    Reserving(ReserveFuture),
+}
 
 /// A writer for the body of an HTTP request or response.
 ///
@@ -14,7 +30,89 @@ use tokio::sync::mpsc::error::TrySendError;
 ///
 /// In order for the reader to stop reading, the writer must be dropped.
 pub struct BodyWriter {
-    pub(crate) sender: tokio::sync::mpsc::Sender<Bytes>,
+    pub(crate) sender: tokio::sync::mpsc::Sender<Message>,
+    /// Sender for the out-of-band error used by [`BodyWriter::abort`].
+    pub(crate) error: Option<tokio::sync::oneshot::Sender<crate::BodyIoError>>,
+    /// Bytes still allowed when an exact content length was declared.
+    pub(crate) remaining: Option<u64>,
+    /// In-flight channel reservation for the [`AsyncWrite`](tokio::io::AsyncWrite) path.
+    pub(crate) write_state: WriteState,
+    /// In-flight `closed()` future backing [`BodyWriter::poll_reader_alive`].
+    pub(crate) reader_closed: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl BodyWriter {
+    /// Abort the body with an error instead of ending it cleanly.
+    ///
+    /// The data channel is closed and `err` is delivered out of band, so the
+    /// reader yields `Poll::Ready(Some(Err(err)))` after draining any data it
+    /// has already received. Use this when the producing task fails partway
+    /// through so consumers can distinguish a truncated body from a complete one.
+    pub fn abort(mut self, err: crate::BodyIoError) {
+        if let Some(error) = self.error.take() {
+            // A failed send means the reader is already gone; nothing to report.
+            let _ = error.send(err);
+        }
+    }
+
+    /// Convenience wrapper around [`abort`](Self::abort) that boxes an
+    /// arbitrary error as the [`BodyIoError`](crate::BodyIoError) source.
+    pub fn abort_with(self, err: impl Into<Box<dyn std::error::Error + Send + Sync>>) {
+        self.abort(crate::BodyIoError::with_source(err));
+    }
+
+    /// Resolves once the [`BodyReader`](crate::BodyReader) half has been dropped.
+    ///
+    /// A long-running producer can `select!` its compute future against this so
+    /// it can stop work early when the consumer has hung up, instead of only
+    /// finding out on the next write with a `BrokenPipe`.
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+
+    /// Poll whether the reader has disconnected.
+    ///
+    /// Returns `Poll::Ready(())` once the [`BodyReader`](crate::BodyReader) has
+    /// been dropped and `Poll::Pending` while it is still alive.
+    pub fn poll_reader_alive(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.sender.is_closed() {
+            self.reader_closed = None;
+            return Poll::Ready(());
+        }
+        // Keep a single `closed()` future alive across polls; a future built
+        // fresh each call would deregister its waker on drop and never fire.
+        // A cloned sender keeps the future `'static`; the clone is stored in
+        // this writer and dropped with it, so it does not delay the reader's
+        // end of stream.
+        let closed = self.reader_closed.get_or_insert_with(|| {
+            let sender = self.sender.clone();
+            Box::pin(async move { sender.closed().await })
+        });
+        match closed.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.reader_closed = None;
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Reserve `len` bytes against the declared content length, returning an
+    /// error if writing `len` more bytes would exceed it.
+    fn reserve(&mut self, len: usize) -> std::io::Result<()> {
+        if let Some(remaining) = self.remaining.as_mut() {
+            match remaining.checked_sub(len as u64) {
+                Some(left) => *remaining = left,
+                None => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "write exceeds declared content length",
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl std::fmt::Debug for BodyWriter {
@@ -23,23 +121,34 @@ impl std::fmt::Debug for BodyWriter {
     }
 }
 
+impl BodyWriter {
+    /// Send a final set of trailing headers after the body data.
+    ///
+    /// The trailers are queued after every chunk written so far, so the reader
+    /// observes them as a single [`http_body::Frame::trailers`] once the data
+    /// stream has drained. Dropping the writer without calling this simply ends
+    /// the body with no trailers.
+    pub async fn send_trailers(&mut self, trailers: http::HeaderMap) -> std::io::Result<()> {
+        self.sender
+            .send(Message::Trailers(Box::new(trailers)))
+            .await
+            .map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::BrokenPipe, "BodyWriter closed")
+            })
+    }
+}
+
 impl std::io::Write for BodyWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let mut bytes = Bytes::copy_from_slice(buf);
-        loop {
-            match self.sender.try_send(bytes) {
-                Ok(()) => return Ok(buf.len()),
-                Err(TrySendError::Full(bytes_ret)) => {
-                    bytes = bytes_ret;
-                    std::thread::yield_now();
-                }
-                Err(TrySendError::Closed(_)) => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::BrokenPipe,
-                        "BodyWriter closed",
-                    ));
-                }
-            }
+        self.reserve(buf.len())?;
+        // Park the thread on the channel's capacity instead of spinning on
+        // `try_send`; this is the blocking counterpart of `poll_write`.
+        match self.sender.blocking_send(Message::Data(Bytes::copy_from_slice(buf))) {
+            Ok(()) => Ok(buf.len()),
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "BodyWriter closed",
+            )),
         }
     }
     fn flush(&mut self) -> std::io::Result<()> {
@@ -53,17 +162,36 @@ impl tokio::io::AsyncWrite for BodyWriter {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<Result<usize, std::io::Error>> {
-        let mut this = pin!(self.sender.send(Bytes::copy_from_slice(buf)));
-        match this.as_mut().poll(cx) {
-            Poll::Pending => {
-                cx.waker().wake_by_ref();
-                Poll::Pending
+        let this = self.get_mut();
+        loop {
+            match &mut this.write_state {
+                WriteState::Idle => {
+                    // Charge the content-length budget once, when a write
+                    // begins, not on every re-poll of the same buffer.
+                    if let Err(err) = this.reserve(buf.len()) {
+                        return Poll::Ready(Err(err));
+                    }
+                    this.write_state =
+                        WriteState::Reserving(Box::pin(this.sender.clone().reserve_owned()));
+                }
+                WriteState::Reserving(reserve) => match reserve.as_mut().poll(cx) {
+                    // The reserve future keeps our waker parked on the channel
+                    // until a slot frees up; it is held across polls.
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(permit)) => {
+                        permit.send(Message::Data(Bytes::copy_from_slice(buf)));
+                        this.write_state = WriteState::Idle;
+                        return Poll::Ready(Ok(buf.len()));
+                    }
+                    Poll::Ready(Err(_)) => {
+                        this.write_state = WriteState::Idle;
+                        return Poll::Ready(Err(std::io::Error::new(
+                            std::io::ErrorKind::BrokenPipe,
+                            "BodyWriter closed",
+                        )));
+                    }
+                },
             }
-            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.len())),
-            Poll::Ready(Err(_)) => Poll::Ready(Err(std::io::Error::new(
-                std::io::ErrorKind::BrokenPipe,
-                "BodyWriter closed",
-            ))),
         }
     }
     fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
@@ -0,0 +1,163 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_compression::tokio::write::{BrotliEncoder, GzipEncoder};
+
+use crate::BodyWriter;
+
+/// A content encoding that a [`CompressWriter`] can apply to the body stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    Brotli,
+}
+
+/// Like [`channel`](crate::channel), but the writer compresses the body on the
+/// fly with `encoding`.
+///
+/// The reader observes the compressed bytes as ordinary data frames, so the
+/// response must advertise the matching `Content-Encoding` header.
+pub fn channel_compressed(encoding: Encoding) -> (crate::BodyReader, CompressWriter) {
+    let (reader, writer) = crate::channel();
+    (reader, writer.compress(encoding))
+}
+
+impl BodyWriter {
+    /// Wrap this writer so everything written is gzip-compressed before being
+    /// pushed into the channel.
+    pub fn gzip(self) -> CompressWriter {
+        self.compress(Encoding::Gzip)
+    }
+
+    /// Wrap this writer so everything written is brotli-compressed before being
+    /// pushed into the channel.
+    pub fn brotli(self) -> CompressWriter {
+        self.compress(Encoding::Brotli)
+    }
+
+    /// Wrap this writer with the encoder for `encoding`.
+    pub fn compress(self, encoding: Encoding) -> CompressWriter {
+        let inner = match encoding {
+            Encoding::Gzip => Inner::Gzip(GzipEncoder::new(self)),
+            Encoding::Brotli => Inner::Brotli(BrotliEncoder::new(self)),
+        };
+        CompressWriter { inner: Some(inner) }
+    }
+}
+
+enum Inner {
+    Gzip(GzipEncoder<BodyWriter>),
+    Brotli(BrotliEncoder<BodyWriter>),
+}
+
+/// A [`BodyWriter`] that compresses the body stream as it is written.
+///
+/// Each write makes a best-effort flush through the encoder so compressed
+/// chunks are emitted promptly for streaming responses. Prefer
+/// [`finish`](Self::finish) (or [`shutdown`](tokio::io::AsyncWriteExt::shutdown))
+/// to emit the trailing compressed block and surface any final I/O error. If
+/// the writer is dropped without finishing, the trailing block is finalized on
+/// a best-effort basis on a detached task, provided a Tokio runtime is running.
+pub struct CompressWriter {
+    inner: Option<Inner>,
+}
+
+impl std::fmt::Debug for CompressWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CompressWriter").finish()
+    }
+}
+
+impl CompressWriter {
+    /// Finalize the compressed stream, flushing the trailing block so the reader
+    /// sees a complete, valid body.
+    pub async fn finish(mut self) -> std::io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+        // Take the encoder so the `Drop` impl does not try to finalize again.
+        match self.inner.take() {
+            Some(Inner::Gzip(mut enc)) => enc.shutdown().await,
+            Some(Inner::Brotli(mut enc)) => enc.shutdown().await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for CompressWriter {
+    fn drop(&mut self) {
+        // Best-effort finalize: if the caller never called `finish`, flush the
+        // trailing compressed block so the reader still sees a valid stream.
+        // The encoder's shutdown is async, so it runs on a detached task; this
+        // requires a Tokio runtime, and without one we cannot finalize.
+        let Some(inner) = self.inner.take() else {
+            return;
+        };
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                use tokio::io::AsyncWriteExt;
+                match inner {
+                    Inner::Gzip(mut enc) => {
+                        let _ = enc.shutdown().await;
+                    }
+                    Inner::Brotli(mut enc) => {
+                        let _ = enc.shutdown().await;
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Run `$body` against whichever encoder backs `$self`, bound to `$enc`.
+///
+/// The encoder is only absent once the writer has been finished or dropped, at
+/// which point the [`AsyncWrite`](tokio::io::AsyncWrite) methods are no longer
+/// reachable.
+macro_rules! with_encoder {
+    ($self:expr, $enc:ident => $body:expr) => {
+        match &mut $self.inner {
+            Some(Inner::Gzip($enc)) => $body,
+            Some(Inner::Brotli($enc)) => $body,
+            None => unreachable!("CompressWriter used after finish"),
+        }
+    };
+}
+
+impl tokio::io::AsyncWrite for CompressWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        with_encoder!(this, enc => {
+            let written = match Pin::new(&mut *enc).poll_write(cx, buf) {
+                Poll::Ready(Ok(written)) => written,
+                other => return other,
+            };
+            // Flush eagerly so a streaming consumer tends to see this chunk
+            // without waiting for the encoder's internal buffer to fill. The
+            // bytes are already buffered in the encoder, so we report them
+            // written regardless; a pending flush simply completes on a later
+            // poll, but a flush error (e.g. the reader hung up) is propagated.
+            match Pin::new(&mut *enc).poll_flush(cx) {
+                Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                _ => Poll::Ready(Ok(written)),
+            }
+        })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        let this = self.get_mut();
+        with_encoder!(this, enc => Pin::new(enc).poll_flush(cx))
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        let this = self.get_mut();
+        with_encoder!(this, enc => Pin::new(enc).poll_shutdown(cx))
+    }
+}
@@ -1,126 +1,125 @@
-use std::{
-    future::Future,
-    pin::Pin,
-    task::{Context, Poll},
-};
-
 use bytes::Bytes;
-use tokio::sync::mpsc::{error::TrySendError, Receiver};
-
-pub struct BodyIoError;
 
-pub fn channel() -> (BodyReader, BodyWriter) {
-    let (tx, rx) = tokio::sync::mpsc::channel(1);
-    (BodyReader { receiver: rx }, BodyWriter { sender: tx })
+mod body_reader;
+mod body_writer;
+
+pub use body_reader::BodyReader;
+pub use body_writer::BodyWriter;
+
+#[cfg(feature = "compression")]
+mod compress;
+#[cfg(feature = "compression")]
+pub use compress::{channel_compressed, CompressWriter, Encoding};
+
+/// The error type used by [`BodyReader`] and [`BodyWriter`].
+///
+/// A clean end of stream is reported as `Poll::Ready(None)`; a [`BodyIoError`]
+/// is only produced when the writer explicitly [`aborts`](BodyWriter::abort)
+/// the body, so consumers can tell a complete body from a truncated one. The
+/// optional source carries the underlying failure when available.
+pub struct BodyIoError {
+    source: Option<Box<dyn std::error::Error + Send + Sync>>,
 }
 
-impl std::fmt::Display for BodyIoError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "BodyIoError")
+impl BodyIoError {
+    /// Create an error with no underlying source.
+    pub fn new() -> Self {
+        BodyIoError { source: None }
     }
-}
 
-impl std::fmt::Debug for BodyIoError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "BodyIoError")
+    /// Wrap an arbitrary error as the source of a [`BodyIoError`].
+    pub fn with_source(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        BodyIoError {
+            source: Some(source.into()),
+        }
     }
 }
 
-impl std::error::Error for BodyIoError {}
-
-pub struct BodyReader {
-    receiver: Receiver<Bytes>,
+impl Default for BodyIoError {
+    fn default() -> Self {
+        BodyIoError::new()
+    }
 }
 
-impl http_body::Body for BodyReader {
-    type Data = Bytes;
-    type Error = BodyIoError;
-    fn poll_frame(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-    ) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
-        let this = self.get_mut();
-        match this.receiver.poll_recv(cx) {
-            Poll::Ready(Some(bytes)) => {
-                let frame = http_body::Frame::data(bytes);
-                Poll::Ready(Some(Ok(frame)))
-            }
-            Poll::Ready(None) => Poll::Ready(None),
-            Poll::Pending => Poll::Pending,
-        }
-    }
+/// An item queued on the internal channel.
+///
+/// The body is a stream of [`Message::Data`] chunks optionally followed by a
+/// single [`Message::Trailers`] carrying the trailing headers. Keeping both on
+/// the same channel preserves their relative ordering without a second
+/// synchronisation primitive.
+pub(crate) enum Message {
+    Data(Bytes),
+    Trailers(Box<http::HeaderMap>),
 }
 
-impl tokio::io::AsyncRead for BodyReader {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut tokio::io::ReadBuf<'_>,
-    ) -> Poll<std::io::Result<()>> {
-        let mut this = Box::pin(self.receiver.recv());
-        match this.as_mut().poll(cx) {
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(Some(bytes)) => {
-                buf.put_slice(&bytes);
-                Poll::Ready(Ok(()))
-            }
-            Poll::Ready(None) => Poll::Ready(Ok(())),
-        }
-    }
+pub fn channel() -> (BodyReader, BodyWriter) {
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    let (err_tx, err_rx) = tokio::sync::oneshot::channel();
+    (
+        BodyReader {
+            receiver: rx,
+            error: err_rx,
+            remaining: None,
+            buffer: Bytes::new(),
+        },
+        BodyWriter {
+            sender: tx,
+            error: Some(err_tx),
+            remaining: None,
+            write_state: body_writer::WriteState::Idle,
+            reader_closed: None,
+        },
+    )
 }
 
-pub struct BodyWriter {
-    sender: tokio::sync::mpsc::Sender<Bytes>,
+/// Like [`channel`], but records an exact content length for the body.
+///
+/// The resulting [`BodyReader`] reports `len` through [`http_body::Body::size_hint`]
+/// so servers can set a `Content-Length` header instead of falling back to
+/// chunked encoding. The [`BodyWriter`] errors if more than `len` bytes are
+/// written, surfacing a mismatch rather than producing a malformed response.
+pub fn channel_with_length(len: u64) -> (BodyReader, BodyWriter) {
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    let (err_tx, err_rx) = tokio::sync::oneshot::channel();
+    (
+        BodyReader {
+            receiver: rx,
+            error: err_rx,
+            remaining: Some(len),
+            buffer: Bytes::new(),
+        },
+        BodyWriter {
+            sender: tx,
+            error: Some(err_tx),
+            remaining: Some(len),
+            write_state: body_writer::WriteState::Idle,
+            reader_closed: None,
+        },
+    )
 }
 
-impl std::io::Write for BodyWriter {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let mut bytes = Bytes::copy_from_slice(buf);
-        loop {
-            match self.sender.try_send(bytes) {
-                Ok(()) => return Ok(buf.len()),
-                Err(TrySendError::Full(bytes_ret)) => {
-                    bytes = bytes_ret;
-                    std::thread::yield_now();
-                }
-                Err(TrySendError::Closed(_)) => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::BrokenPipe,
-                        "BodyWriter closed",
-                    ));
-                }
-            }
+impl std::fmt::Display for BodyIoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "BodyIoError: {source}"),
+            None => write!(f, "BodyIoError"),
         }
     }
-    fn flush(&mut self) -> std::io::Result<()> {
-        Ok(())
-    }
 }
 
-impl tokio::io::AsyncWrite for BodyWriter {
-    fn poll_write(
-        self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &[u8],
-    ) -> Poll<Result<usize, std::io::Error>> {
-        let mut this = Box::pin(self.sender.send(Bytes::copy_from_slice(buf)));
-        match this.as_mut().poll(cx) {
-            Poll::Pending => Poll::Pending,
-            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.len())),
-            Poll::Ready(Err(_)) => Poll::Ready(Err(std::io::Error::new(
-                std::io::ErrorKind::BrokenPipe,
-                "BodyWriter closed",
-            ))),
-        }
-    }
-    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
-        Poll::Ready(Ok(()))
+impl std::fmt::Debug for BodyIoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BodyIoError")
+            .field("source", &self.source)
+            .finish()
     }
-    fn poll_shutdown(
-        self: Pin<&mut Self>,
-        _cx: &mut Context<'_>,
-    ) -> Poll<Result<(), std::io::Error>> {
-        Poll::Ready(Ok(()))
+}
+
+impl std::error::Error for BodyIoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn std::error::Error + 'static))
     }
 }
 
@@ -181,4 +180,45 @@ mod tests {
 
         writer_thread.join().unwrap();
     }
+
+    #[tokio::test]
+    async fn test_trailers_follow_data() {
+        use futures::StreamExt;
+
+        use tokio::io::AsyncWriteExt;
+
+        let (reader, mut writer) = channel();
+
+        let writer_thread = tokio::spawn(async move {
+            writer.write_all(b"Hello, ").await.unwrap();
+            let mut trailers = http::HeaderMap::new();
+            trailers.insert("grpc-status", http::HeaderValue::from_static("0"));
+            writer.send_trailers(trailers).await.unwrap();
+        });
+
+        let mut stream = http_body_util::BodyStream::new(reader);
+
+        let mut body = Vec::new();
+        let mut trailers = None;
+        while let Some(Ok(frame)) = stream.next().await {
+            if let Some(bytes) = frame.data_ref() {
+                body.extend_from_slice(bytes);
+            } else if let Ok(map) = frame.into_trailers() {
+                trailers = Some(map);
+            }
+        }
+
+        assert_eq!(body, b"Hello, ");
+        let trailers = trailers.expect("trailers frame");
+        assert_eq!(trailers["grpc-status"], "0");
+
+        writer_thread.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_closed_on_reader_drop() {
+        let (reader, writer) = channel();
+        drop(reader);
+        writer.closed().await;
+    }
 }